@@ -20,6 +20,53 @@ impl fmt::Display for AnyResult {
 
 impl Error for AnyResult {}
 
+/// A portal edge connecting two rooms with a fixed traversal cost. Unlike the
+/// cardinal exits from `game::map::describe_exits`, the two ends can be
+/// arbitrarily far apart on the map (including intra-shard portals that jump
+/// across large distances).
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub struct PortalEdge {
+    /// the room the portal is entered from
+    pub from: RoomName,
+    /// the room the portal exits into
+    pub to: RoomName,
+    /// the cost of traversing the portal
+    pub cost: u32,
+}
+
+/// The quantity a search minimizes.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub enum Objective {
+    /// Minimize the number of rooms crossed: every passable room counts as cost 1
+    /// and a portal hop also counts as 1, while `u8::MAX` from the `cost_callback` is
+    /// still honored as impassable.
+    Hops,
+    /// Minimize the summed `cost_callback` weight (the default behaviour).
+    #[default]
+    Weighted,
+}
+
+impl Objective {
+    /// the cost charged for entering a single passable room with the given raw
+    /// `cost_callback` weight under this objective
+    fn room_cost(&self, raw: u8) -> u32 {
+        match self {
+            Objective::Hops => 1,
+            Objective::Weighted => raw as u32,
+        }
+    }
+
+    /// the cost charged for traversing a portal edge of raw cost `cost` under this
+    /// objective. Under `Hops` a portal crosses into one room, so it counts as a
+    /// single hop; under `Weighted` its raw cost is used as-is.
+    fn portal_cost(&self, cost: u32) -> u32 {
+        match self {
+            Objective::Hops => 1,
+            Objective::Weighted => cost,
+        }
+    }
+}
+
 #[derive(Copy, Clone, Eq, PartialEq, Debug)]
 struct PortalRouterOpenSetEntry {
     /// the room in the open set
@@ -53,8 +100,10 @@ impl PortalRouterOpenSetEntry {
         g_score: u32,
         open_dir: Option<Direction>,
         goals: &HashSet<RoomName>,
+        portals: &[PortalEdge],
+        objective: Objective,
     ) -> Self {
-        let heuristic_cost = get_heuristic_cost_to_closest_goal(room, goals);
+        let heuristic_cost = get_heuristic_cost_to_closest_goal(room, goals, portals, objective);
 
         PortalRouterOpenSetEntry {
             room,
@@ -65,12 +114,42 @@ impl PortalRouterOpenSetEntry {
     }
 }
 
-/// Find cost as the lowest manhattan distance to any goal
-fn get_heuristic_cost_to_closest_goal(room: RoomName, goals: &HashSet<RoomName>) -> u32 {
+/// manhattan distance in rooms between two room names
+fn manhattan_distance(a: RoomName, b: RoomName) -> u32 {
+    a.x_coord().abs_diff(b.x_coord()) + a.y_coord().abs_diff(b.y_coord())
+}
+
+/// Find cost as the lowest manhattan distance to any goal, allowing for a single
+/// portal hop. For each goal we take the cheaper of the direct manhattan distance
+/// and the best `manhattan(room -> entrance) + portal cost + manhattan(exit -> goal)`
+/// over all portals; a portal can teleport close to a goal for cheap, so folding it
+/// into the estimate lowers the heuristic where needed so it does not overestimate a
+/// one-portal route.
+///
+/// Note this only folds in a *single* portal hop. When the optimal route chains two or
+/// more portals, the one-portal estimate can exceed the true cost, so the heuristic is
+/// only guaranteed admissible for routes that use at most one portal; with longer portal
+/// chains A* may settle on a suboptimal goal.
+fn get_heuristic_cost_to_closest_goal(
+    room: RoomName,
+    goals: &HashSet<RoomName>,
+    portals: &[PortalEdge],
+    objective: Objective,
+) -> u32 {
+    // manhattan counts rooms; both objectives charge at least 1 per passable room (see
+    // the `cost_callback >= 1` precondition on `find_route`), so the room-count estimate
+    // is a valid lower bound without any scaling
     let mut lowest_cost = u32::MAX;
     for goal in goals {
-        let cost =
-            room.x_coord().abs_diff(goal.x_coord()) + room.y_coord().abs_diff(goal.y_coord());
+        let mut cost = manhattan_distance(room, *goal);
+        for portal in portals {
+            let via_portal = manhattan_distance(room, portal.from)
+                .saturating_add(objective.portal_cost(portal.cost))
+                .saturating_add(manhattan_distance(portal.to, *goal));
+            if via_portal < cost {
+                cost = via_portal;
+            }
+        }
         if cost < lowest_cost {
             lowest_cost = cost;
         }
@@ -78,79 +157,378 @@ fn get_heuristic_cost_to_closest_goal(room: RoomName, goals: &HashSet<RoomName>)
     lowest_cost
 }
 
-/// navigate backwards across our map of where tiles came from to construct a path
+/// navigate backwards across our map of where each room came from to construct an
+/// ordered path from the origin to `room_name`. We track the predecessor room
+/// directly (rather than a cardinal direction) so that non-adjacent portal hops can
+/// be reconstructed the same way as single-room steps.
 fn resolve_completed_path(
     room_name: RoomName,
-    visited: &HashMap<RoomName, Option<Direction>>,
-) -> HashSet<RoomName> {
-    let mut path = HashSet::new();
-    path.insert(room_name);
+    came_from: &HashMap<RoomName, Option<RoomName>>,
+) -> Vec<RoomName> {
+    let mut path = vec![room_name];
 
     let mut cursor_room = room_name;
 
-    while let Some(optional_search_direction) = visited.get(&cursor_room) {
-        match optional_search_direction {
-            Some(search_dir) => {
-                if let Some(next_room) = cursor_room.checked_add((-*search_dir).into()) {
-                    path.insert(next_room);
-                    cursor_room = next_room;
-                }
-            }
-            None => break,
-        }
+    while let Some(Some(prev_room)) = came_from.get(&cursor_room) {
+        path.push(*prev_room);
+        cursor_room = *prev_room;
     }
 
+    // we walked goal -> origin following came_from, so flip it to origin -> goal
+    path.reverse();
     path
 }
 
+/// advance `indices` to the next lexicographic permutation in place, returning
+/// false once the final (fully descending) permutation has been passed
+fn next_permutation(indices: &mut [usize]) -> bool {
+    let n = indices.len();
+    if n < 2 {
+        return false;
+    }
+    let mut i = n - 1;
+    while i > 0 && indices[i - 1] >= indices[i] {
+        i -= 1;
+    }
+    if i == 0 {
+        return false;
+    }
+    let mut j = n - 1;
+    while indices[j] <= indices[i - 1] {
+        j -= 1;
+    }
+    indices.swap(i - 1, j);
+    indices[i..].reverse();
+    true
+}
+
+/// enumerate every ordering of `items` in lexicographic order of the index
+/// permutation, mirroring the long-range router's `--permute` mode. An empty
+/// slice yields a single empty ordering.
+fn permutations(items: &[RoomName]) -> Vec<Vec<RoomName>> {
+    let mut indices: Vec<usize> = (0..items.len()).collect();
+    let mut orderings = Vec::new();
+    loop {
+        orderings.push(indices.iter().map(|&i| items[i]).collect());
+        if !next_permutation(&mut indices) {
+            break;
+        }
+    }
+    orderings
+}
+
 pub struct PortalRouterOps;
 
 impl PortalRouterOps {
+    /// Run the A* search from `origin` to the closest of `goals`, expanding both
+    /// cardinal room exits and `portals` as out-edges.
+    ///
+    /// `beam_width` bounds the frontier: after each expansion round only the
+    /// `beam_width` lowest-`f_score` open-set entries are retained and the rest are
+    /// discarded, capping the size of the open set. The total number of nodes expanded
+    /// per search is not capped: a discarded room is forgotten and can be discovered and
+    /// expanded again later. This trades path optimality for a bounded frontier - passing
+    /// `None` keeps the frontier unbounded and the search exact.
+    ///
+    /// `objective` selects what is minimized: [`Objective::Hops`] for the fewest rooms
+    /// crossed or [`Objective::Weighted`] for the lowest summed `cost_callback` weight.
+    ///
+    /// `cost_callback` returns `u8::MAX` for impassable rooms. Under
+    /// [`Objective::Weighted`] every passable room must cost at least `1`: the
+    /// manhattan heuristic assumes a minimum per-room cost of one unit, so a passable
+    /// room reporting `0` makes the heuristic inadmissible and the returned route may
+    /// not be the cheapest. Callers that need zero-cost rooms should use
+    /// [`Objective::Hops`] or bias their costs up by one.
     pub fn find_route<F: Fn(&RoomName) -> u8>(
         origin: RoomName,
         goals: HashSet<RoomName>,
+        portals: &[PortalEdge],
+        beam_width: Option<usize>,
+        objective: Objective,
         cost_callback: F,
-    ) -> Result<HashSet<RoomName>, AnyResult> {
+    ) -> Result<(Vec<RoomName>, u32), AnyResult> {
         let mut open_set = BinaryHeap::new();
-        // visited hashmap contains the direction we visited the room from, for backtracking once we find a path
-        let mut visited = HashMap::new();
+        // came_from contains the room we visited each room from, for backtracking once we find a path
+        let mut came_from = HashMap::new();
+        // g_score tracks the cost of the best known path to each room, for relaxation
+        let mut g_score = HashMap::new();
 
-        open_set.push(PortalRouterOpenSetEntry::new(origin, 0, None, &goals));
-        visited.insert(origin, None);
+        open_set.push(PortalRouterOpenSetEntry::new(
+            origin, 0, None, &goals, portals, objective,
+        ));
+        came_from.insert(origin, None);
+        g_score.insert(origin, 0);
 
         while let Some(open_set_entry) = open_set.pop() {
+            // lazy deletion: BinaryHeap has no decrease-key, so a stale entry may still be
+            // sitting in the open set after we found a cheaper path to the same room - skip it
+            if g_score
+                .get(&open_set_entry.room)
+                .is_some_and(|&best| open_set_entry.g_score > best)
+            {
+                continue;
+            }
+
+            // only test goal membership on pop, so the first (cheapest) path to the goal wins
+            if goals.contains(&open_set_entry.room) {
+                let path = resolve_completed_path(open_set_entry.room, &came_from);
+                return Ok((path, open_set_entry.g_score));
+            }
+
             for direction in game::map::describe_exits(open_set_entry.room).keys() {
                 // skip this direction quickly if it's toward the room that opened this entry
                 if Some(-direction) == open_set_entry.open_dir {
                     continue;
                 }
                 if let Some(adj_room_name) = open_set_entry.room.checked_add(direction.into()) {
-                    if visited.contains_key(&adj_room_name) {
+                    let adj_traverse_cost = cost_callback(&adj_room_name);
+                    // impassable rooms are never expanded, but a goal is always a valid terminal
+                    if adj_traverse_cost == u8::MAX && !goals.contains(&adj_room_name) {
                         continue;
                     }
 
-                    // unvisited; check if goal first, then add open set entry if passable
-                    visited.insert(adj_room_name, Some(direction));
+                    let tentative_g =
+                        open_set_entry.g_score + objective.room_cost(adj_traverse_cost);
 
-                    if goals.contains(&adj_room_name) {
-                        // we've found a goal; get the path back to it
-                        let path = resolve_completed_path(adj_room_name, &visited);
-                        return Ok(path);
-                    }
-
-                    let adj_traverse_cost = cost_callback(&adj_room_name);
-                    if adj_traverse_cost < u8::MAX {
+                    // relax: only keep this edge when it strictly improves on the best known
+                    // path to the neighbor (or the neighbor hasn't been seen yet)
+                    if g_score
+                        .get(&adj_room_name)
+                        .is_none_or(|&best| tentative_g < best)
+                    {
+                        came_from.insert(adj_room_name, Some(open_set_entry.room));
+                        g_score.insert(adj_room_name, tentative_g);
                         open_set.push(PortalRouterOpenSetEntry::new(
                             adj_room_name,
-                            open_set_entry.g_score + adj_traverse_cost as u32,
+                            tentative_g,
                             Some(direction),
                             &goals,
+                            portals,
+                            objective,
                         ));
                     }
                 }
             }
+
+            // expand portal edges leaving this room as additional out-edges; their far
+            // end can be an arbitrary distance away, so there's no cardinal open_dir
+            for portal in portals {
+                if portal.from != open_set_entry.room {
+                    continue;
+                }
+                let exit_room = portal.to;
+                let exit_traverse_cost = cost_callback(&exit_room);
+                // a portal into an impassable room is no more usable than a cardinal step into one
+                if exit_traverse_cost == u8::MAX && !goals.contains(&exit_room) {
+                    continue;
+                }
+
+                // charge both the portal traversal and the cost of the room it exits into,
+                // matching the cardinal cost model so g_score stays comparable across edge types
+                let tentative_g = open_set_entry.g_score
+                    + objective.portal_cost(portal.cost)
+                    + objective.room_cost(exit_traverse_cost);
+
+                if g_score
+                    .get(&exit_room)
+                    .is_none_or(|&best| tentative_g < best)
+                {
+                    came_from.insert(exit_room, Some(open_set_entry.room));
+                    g_score.insert(exit_room, tentative_g);
+                    open_set.push(PortalRouterOpenSetEntry::new(
+                        exit_room,
+                        tentative_g,
+                        None,
+                        &goals,
+                        portals,
+                        objective,
+                    ));
+                }
+            }
+
+            // bound the frontier: drain the heap into a sorted buffer and keep only the
+            // `beam_width` most promising entries, discarding the rest for the next round
+            if let Some(width) = beam_width {
+                if open_set.len() > width {
+                    let mut buffer: Vec<_> = open_set.drain().collect();
+                    buffer.sort_by(|a, b| a.f_score.cmp(&b.f_score));
+                    let pruned = buffer.split_off(width);
+                    // forget the book-keeping for rooms that no longer appear in the beam, so a
+                    // pruned room can be rediscovered and relaxed again later rather than being
+                    // permanently blocked by its own stale g_score (which would make the relaxation
+                    // gate reject every future path and could strand a reachable goal)
+                    let retained: HashSet<RoomName> = buffer.iter().map(|entry| entry.room).collect();
+                    for entry in pruned {
+                        if !retained.contains(&entry.room) {
+                            g_score.remove(&entry.room);
+                            came_from.remove(&entry.room);
+                        }
+                    }
+                    open_set = buffer.into_iter().collect();
+                }
+            }
         }
 
         Err(AnyResult::Fail)
     }
+
+    /// Find the cheapest route from `origin` that visits every room in `waypoints`
+    /// before reaching `final_goal`. The order the waypoints are visited in is not
+    /// fixed: for a small waypoint count we enumerate every permutation of the
+    /// intermediate stops, solve each leg with the single-pair [`find_route`] A*, and
+    /// keep the concatenated route with the lowest summed cost. Per-pair leg results
+    /// are cached so legs shared across permutations aren't recomputed.
+    ///
+    /// [`find_route`]: PortalRouterOps::find_route
+    pub fn find_route_through<F: Fn(&RoomName) -> u8>(
+        origin: RoomName,
+        waypoints: Vec<RoomName>,
+        final_goal: RoomName,
+        portals: &[PortalEdge],
+        beam_width: Option<usize>,
+        objective: Objective,
+        cost_callback: F,
+    ) -> Result<(Vec<RoomName>, u32), AnyResult> {
+        // cache per-pair leg results (None marks a pair we already found unreachable)
+        let mut leg_cache: HashMap<(RoomName, RoomName), Option<(Vec<RoomName>, u32)>> =
+            HashMap::new();
+
+        let mut best: Option<(Vec<RoomName>, u32)> = None;
+
+        for ordering in permutations(&waypoints) {
+            // the full ordered list of stops for this permutation
+            let mut stops = Vec::with_capacity(ordering.len() + 2);
+            stops.push(origin);
+            stops.extend(ordering);
+            stops.push(final_goal);
+
+            let mut route: Vec<RoomName> = Vec::new();
+            let mut total_cost: u32 = 0;
+            let mut feasible = true;
+
+            for leg in stops.windows(2) {
+                let (from, to) = (leg[0], leg[1]);
+                let leg_result = leg_cache.entry((from, to)).or_insert_with(|| {
+                    let goals = HashSet::from([to]);
+                    Self::find_route(from, goals, portals, beam_width, objective, &cost_callback)
+                        .ok()
+                });
+
+                match leg_result {
+                    Some((leg_route, leg_cost)) => {
+                        if route.is_empty() {
+                            route.extend(leg_route.iter().copied());
+                        } else {
+                            // the first room of this leg is the last room of the previous leg
+                            route.extend(leg_route.iter().skip(1).copied());
+                        }
+                        total_cost = total_cost.saturating_add(*leg_cost);
+                    }
+                    None => {
+                        feasible = false;
+                        break;
+                    }
+                }
+            }
+
+            if feasible && best.as_ref().is_none_or(|(_, cost)| total_cost < *cost) {
+                best = Some((route, total_cost));
+            }
+        }
+
+        best.ok_or(AnyResult::Fail)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn room(name: &str) -> RoomName {
+        name.parse().expect("valid room name")
+    }
+
+    #[test]
+    fn resolve_completed_path_is_ordered_origin_to_goal() {
+        let origin = room("E0N0");
+        let mid = room("E1N0");
+        let goal = room("E2N0");
+
+        let mut came_from = HashMap::new();
+        came_from.insert(origin, None);
+        came_from.insert(mid, Some(origin));
+        came_from.insert(goal, Some(mid));
+
+        // the reconstructed path runs origin -> goal, not goal -> origin
+        assert_eq!(resolve_completed_path(goal, &came_from), vec![origin, mid, goal]);
+    }
+
+    #[test]
+    fn objective_room_cost_distinguishes_hops_and_weighted() {
+        assert_eq!(Objective::Hops.room_cost(7), 1);
+        assert_eq!(Objective::Weighted.room_cost(7), 7);
+    }
+
+    #[test]
+    fn objective_portal_cost_distinguishes_hops_and_weighted() {
+        assert_eq!(Objective::Hops.portal_cost(50), 1);
+        assert_eq!(Objective::Weighted.portal_cost(50), 50);
+    }
+
+    #[test]
+    fn permutations_enumerates_every_unique_ordering() {
+        let a = room("E0N0");
+        let b = room("E1N0");
+        let c = room("E2N0");
+
+        let perms = permutations(&[a, b, c]);
+
+        assert_eq!(perms.len(), 6);
+        // lexicographic by index: input order first, fully reversed last
+        assert_eq!(perms[0], vec![a, b, c]);
+        assert_eq!(perms[5], vec![c, b, a]);
+        let unique: HashSet<Vec<RoomName>> = perms.iter().cloned().collect();
+        assert_eq!(unique.len(), 6);
+    }
+
+    #[test]
+    fn permutations_of_empty_is_a_single_empty_ordering() {
+        assert_eq!(permutations(&[]), vec![Vec::<RoomName>::new()]);
+    }
+
+    #[test]
+    fn heuristic_without_portals_is_direct_manhattan() {
+        let origin = room("E0N0");
+        let goal = room("E3N4");
+        let goals = HashSet::from([goal]);
+
+        let expected = manhattan_distance(origin, goal);
+        assert_eq!(
+            get_heuristic_cost_to_closest_goal(origin, &goals, &[], Objective::Weighted),
+            expected
+        );
+    }
+
+    #[test]
+    fn heuristic_folds_in_a_cheaper_portal_per_objective() {
+        // a portal whose entrance is the origin and whose exit is the goal makes the
+        // portal cost dominate the estimate; under Hops it's one hop, under Weighted it's raw
+        let origin = room("E0N0");
+        let goal = room("E50N0");
+        let goals = HashSet::from([goal]);
+        let portals = [PortalEdge {
+            from: origin,
+            to: goal,
+            cost: 40,
+        }];
+
+        assert_eq!(
+            get_heuristic_cost_to_closest_goal(origin, &goals, &portals, Objective::Hops),
+            1
+        );
+        assert_eq!(
+            get_heuristic_cost_to_closest_goal(origin, &goals, &portals, Objective::Weighted),
+            40
+        );
+    }
 }